@@ -21,7 +21,7 @@ extern crate test;
 #[cfg(test)]
 extern crate "iron-test" as itest;
 
-pub use mount::{Mount, VirtualRoot, OriginalUrl, NoMatch};
+pub use mount::{Mount, VirtualRoot, OriginalUrl, NoMatch, Pass, TrailingSlash, NormalizeMode};
 
 mod mount;
 
@@ -1,5 +1,6 @@
 use std::error::Error;
-use iron::{Handler, Response, Request, IronResult, IronError, Url, status};
+use std::collections::HashMap;
+use iron::{Handler, Response, Request, IronResult, IronError, Url, status, method, headers};
 use iron::typemap;
 use sequence_trie::SequenceTrie;
 use std::fmt;
@@ -26,14 +27,78 @@ impl typemap::Key for VirtualRoot { type Value = Url; }
 /// Mounted handlers may also access the *original* URL by requesting the `OriginalUrl` key
 /// from `Request::extensions`.
 pub struct Mount {
-    inner: SequenceTrie<String, Match>
+    inner: SequenceTrie<String, Match>,
+    normalize: TrailingSlash,
+    names: HashMap<String, Vec<String>>,
+    // Every distinct route key ever inserted into `inner`, in insertion
+    // order. `SequenceTrie` has no way to enumerate its own entries, so this
+    // is what `rebase`/`extend` walk to relocate a built `Mount`.
+    keys: Vec<Vec<String>>
+}
+
+/// Controls how `Mount` treats a trailing slash on an incoming path before
+/// it tries to match a mounted handler. Set with `Mount::normalize`.
+pub enum TrailingSlash {
+    /// Leave the path exactly as given. This is the default.
+    Ignore,
+    /// Rewrite the path in place to remove a trailing slash before dispatch.
+    Trim,
+    /// Rewrite the path in place to add a trailing slash before dispatch.
+    Append,
+    /// Don't rewrite the path directly; instead, whenever the incoming path
+    /// isn't already in normalized form *and* the normalized form matches a
+    /// mounted handler, short-circuit dispatch and respond with a redirect
+    /// to the normalized URL.
+    Redirect(NormalizeMode)
+}
+
+/// The two ways a path can be brought into normalized form, shared between
+/// the in-place (`Trim`/`Append`) and `Redirect` variants of `TrailingSlash`.
+pub enum NormalizeMode {
+    /// Remove a trailing slash.
+    Trim,
+    /// Add a trailing slash.
+    Append
+}
+
+// Drops any trailing empty path segments, which represent a trailing slash.
+fn trim_trailing_slash(path: &mut Vec<String>) {
+    while path.last().map(|s| &**s) == Some("") {
+        path.pop();
+    }
+}
+
+// Ensures the path ends in exactly one trailing empty segment.
+fn append_trailing_slash(path: &mut Vec<String>) {
+    trim_trailing_slash(path);
+    path.push(String::new());
 }
 
 struct Match {
-    handler: Box<Handler>,
+    // The verb-agnostic handler registered through `on`, used when no
+    // method-specific handler is registered for the incoming request.
+    default: Option<Box<Handler>>,
+    // Per-method handlers registered through `on_method`.
+    methods: HashMap<method::Method, Box<Handler>>,
     length: usize
 }
 
+impl Match {
+    fn new(length: usize) -> Match {
+        Match {
+            default: None,
+            methods: HashMap::new(),
+            length: length
+        }
+    }
+
+    // The handler that should serve `method`, if any: a method-specific
+    // handler takes priority over the verb-agnostic default.
+    fn handler_for(&self, method: &method::Method) -> Option<&Box<Handler>> {
+        self.methods.get(method).or(self.default.as_ref())
+    }
+}
+
 /// The error returned by `Mount` when a request doesn't match any mounted handlers.
 #[derive(Debug)]
 pub struct NoMatch;
@@ -48,42 +113,281 @@ impl fmt::Display for NoMatch {
     }
 }
 
+/// Returned by a mounted `Handler` to decline a request it has been given.
+///
+/// When `Mount` sees a `Pass`, it undoes the URL munging it did for this
+/// mount point and retries the request against the next less-specific
+/// mounted handler, as if the declining handler had never matched at all.
+#[derive(Debug)]
+pub struct Pass;
+
+impl Error for Pass {
+    fn description(&self) -> &'static str { "Pass" }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+// Whether this error should cause `Mount` to fall through to the next
+// shorter matching prefix instead of propagating the error immediately.
+fn is_fallthrough(err: &IronError) -> bool {
+    err.error.is::<NoMatch>() || err.error.is::<Pass>() || err.error.is::<MethodNotAllowed>()
+}
+
+// The error carried by the `MethodNotAllowed` response `Mount` builds when
+// a prefix matches but has no handler for the request's method and no
+// verb-agnostic default. Falls through like `Pass`/`NoMatch`: a less
+// specific mount may still have a handler willing to serve this method.
+#[derive(Debug)]
+struct MethodNotAllowed;
+
+impl Error for MethodNotAllowed {
+    fn description(&self) -> &'static str { "Method Not Allowed" }
+}
+
+impl fmt::Display for MethodNotAllowed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+// Builds the IronError `Mount` falls through on when a prefix matches but
+// has no handler for `req`'s method and no verb-agnostic default.
+fn method_not_allowed(matched: &Match) -> IronError {
+    let mut response = Response::with(status::MethodNotAllowed);
+    response.headers.set(headers::Allow(matched.methods.keys().cloned().collect()));
+    IronError { error: Box::new(MethodNotAllowed) as Box<Error>, response: response }
+}
+
 impl Mount {
     /// Creates a new instance of `Mount`.
     pub fn new() -> Mount {
         Mount {
-            inner: SequenceTrie::new()
+            inner: SequenceTrie::new(),
+            normalize: TrailingSlash::Ignore,
+            names: HashMap::new(),
+            keys: Vec::new()
         }
     }
 
+    /// Configures how `Mount` treats a trailing slash on incoming paths.
+    ///
+    /// Defaults to `TrailingSlash::Ignore`, which preserves the historical
+    /// behavior of matching `/foo` and `/foo/` identically without ever
+    /// rewriting or redirecting.
+    pub fn normalize(&mut self, mode: TrailingSlash) -> &mut Mount {
+        self.normalize = mode;
+        self
+    }
+
     /// Mounts a given `Handler` onto a route.
     ///
     /// This method may be called multiple times with different routes.
     /// For a given request, the *most specific* handler will be selected.
     ///
-    /// Existing handlers on the same route will be overwritten.
+    /// The handler is registered as the verb-agnostic default for this route;
+    /// it serves any request method that doesn't have a handler of its own
+    /// registered through `on_method`. Existing defaults on the same route
+    /// will be overwritten.
     pub fn on<H: Handler>(&mut self, route: &str, handler: H) -> &mut Mount {
-        // Parse the route into a list of strings. The unwrap is safe because strs are UTF-8.
-        let key = Path::new(route).str_components()
-            .map(|s| s.unwrap().to_string()).collect::<Vec<_>>();
-
-        // Insert a match struct into the trie.
-        self.inner.insert(key.as_slice(), Match {
-            handler: Box::new(handler) as Box<Handler>,
-            length: key.len()
-        });
+        self.match_mut(route).default = Some(Box::new(handler) as Box<Handler>);
+        self
+    }
+
+    /// Mounts a given `Handler` onto a route for one specific HTTP method.
+    ///
+    /// This allows the same prefix to dispatch to different handlers
+    /// depending on `req.method`. A request whose method has no handler
+    /// registered here falls back to the verb-agnostic default registered
+    /// through `on`, if any; otherwise this mount point declines with
+    /// `status::MethodNotAllowed` and an `Allow` header listing the methods
+    /// registered for this route, and `Mount` falls through to the next
+    /// less-specific prefix, the same as it would for a handler `Pass`.
+    ///
+    /// Existing handlers for the same route and method will be overwritten.
+    pub fn on_method<H: Handler>(&mut self, method: method::Method, route: &str, handler: H) -> &mut Mount {
+        self.match_mut(route).methods.insert(method, Box::new(handler) as Box<Handler>);
         self
     }
 
+    /// Mounts a given `Handler` onto a route and gives that route a name,
+    /// so a URL to it can later be generated with `url_for` instead of
+    /// duplicating the route string at every call site.
+    ///
+    /// Existing handlers on the same route will be overwritten, as with `on`.
+    pub fn on_named<H: Handler>(&mut self, name: &str, route: &str, handler: H) -> &mut Mount {
+        self.names.insert(name.to_string(), parse_route(route));
+        self.on(route, handler)
+    }
+
     /// The old way to mount handlers.
     #[deprecated = "use .on instead"]
     pub fn mount<H: Handler>(&mut self, route: &str, handler: H) -> &mut Mount {
         self.on(route, handler)
     }
+
+    /// Reconstructs the absolute URL for a mount registered through
+    /// `on_named`, joining its prefix with `tail`. Returns `None` if `name`
+    /// isn't registered.
+    ///
+    /// Since `Mount` isn't bound to any particular request, the returned
+    /// URL uses a placeholder `http://localhost` authority. From inside a
+    /// `Handler`, prefer `url_for_request`, which draws the real authority
+    /// from the request and prepends any prefix already consumed to reach
+    /// this mount.
+    pub fn url_for(&self, name: &str, tail: &[&str]) -> Option<Url> {
+        self.names.get(name).map(|prefix| {
+            let mut url = Url::parse("http://localhost").unwrap();
+            url.path = prefix.iter().cloned()
+                .chain(tail.iter().map(|s| s.to_string()))
+                .collect();
+            url
+        })
+    }
+
+    /// Like `url_for`, but draws its authority from `req` and prepends the
+    /// mount prefix(es) already consumed to reach this `Handler`, so links
+    /// generated from inside a nested mount are correctly fully-qualified.
+    ///
+    /// The consumed prefix is the difference between `OriginalUrl` (the
+    /// whole incoming path, recorded by the outermost `Mount`) and the
+    /// current, already-trimmed `req.url.path` — *not* `VirtualRoot`, whose
+    /// path includes whatever of the request this mount's handler still has
+    /// left to match, not just the prefix that got it here.
+    pub fn url_for_request(&self, req: &Request, name: &str, tail: &[&str]) -> Option<Url> {
+        let original = req.extensions.get::<OriginalUrl>().unwrap_or(&req.url);
+        let consumed = original.path.len() - req.url.path.len();
+
+        self.names.get(name).map(|route| {
+            let mut url = req.url.clone();
+            let mut path = original.path[..consumed].to_vec();
+            path.push_all(route.as_slice());
+            path.extend(tail.iter().map(|s| s.to_string()));
+            url.path = path;
+            url
+        })
+    }
+
+    /// Relocates an already-built `Mount` under a new prefix, returning it
+    /// as a standalone `Mount` that can be mounted or merged like any other.
+    ///
+    /// Every registered route (and named route) is prepended with `prefix`,
+    /// so a sub-app assembled independently can be nested elsewhere without
+    /// re-registering each of its handlers by hand.
+    pub fn rebase(mut self, prefix: &str) -> Mount {
+        let prefix_segments = parse_route(prefix);
+        let mut rebased = Mount::new();
+        rebased.normalize = self.normalize;
+
+        for key in self.keys.drain() {
+            if let Some(mut matched) = self.inner.remove(key.as_slice()) {
+                let mut new_key = prefix_segments.clone();
+                new_key.push_all(key.as_slice());
+                matched.length = new_key.len();
+
+                rebased.inner.insert(new_key.as_slice(), matched);
+                rebased.keys.push(new_key);
+            }
+        }
+
+        for (name, route) in self.names.drain() {
+            let mut new_route = prefix_segments.clone();
+            new_route.push_all(route.as_slice());
+            rebased.names.insert(name, new_route);
+        }
+
+        rebased
+    }
+
+    /// Merges an already-built `Mount` into this one under a new prefix,
+    /// as if every one of its routes had been registered here directly
+    /// with `prefix` prepended. Equivalent to `rebase`-ing `other`
+    /// and copying its routes and names over.
+    pub fn extend(&mut self, other: Mount, prefix: &str) {
+        let mut rebased = other.rebase(prefix);
+
+        for key in rebased.keys.drain() {
+            if let Some(matched) = rebased.inner.remove(key.as_slice()) {
+                if self.inner.get(key.as_slice()).is_none() {
+                    self.inner.insert(key.as_slice(), matched);
+                    self.keys.push(key);
+                } else {
+                    // Merge into the existing route, as `on`/`on_method` would.
+                    let existing = self.inner.get_mut(key.as_slice()).unwrap();
+                    if matched.default.is_some() { existing.default = matched.default; }
+                    existing.methods.extend(matched.methods.into_iter());
+                }
+            }
+        }
+
+        for (name, route) in rebased.names.drain() {
+            self.names.insert(name, route);
+        }
+    }
+
+    // Returns the `Match` for `route`, inserting an empty one if none exists yet.
+    fn match_mut(&mut self, route: &str) -> &mut Match {
+        let key = parse_route(route);
+
+        if self.inner.get(key.as_slice()).is_none() {
+            let length = key.len();
+            self.inner.insert(key.as_slice(), Match::new(length));
+            self.keys.push(key.clone());
+        }
+
+        self.inner.get_mut(key.as_slice()).unwrap()
+    }
+}
+
+// Parses a route into the list of path segments used as a trie key. The
+// unwrap is safe because strs are UTF-8.
+fn parse_route(route: &str) -> Vec<String> {
+    Path::new(route).str_components()
+        .map(|s| s.unwrap().to_string()).collect::<Vec<_>>()
 }
 
 impl Handler for Mount {
     fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        match self.normalize {
+            TrailingSlash::Ignore => {}
+            TrailingSlash::Trim => trim_trailing_slash(&mut req.url.path),
+            TrailingSlash::Append => append_trailing_slash(&mut req.url.path),
+            TrailingSlash::Redirect(ref mode) => {
+                let mut normalized = req.url.path.clone();
+                match *mode {
+                    NormalizeMode::Trim => trim_trailing_slash(&mut normalized),
+                    NormalizeMode::Append => append_trailing_slash(&mut normalized)
+                }
+
+                if normalized != req.url.path {
+                    let mut normalized_root = normalized.as_slice();
+                    while normalized_root.last().map(|s| &**s) == Some("") {
+                        normalized_root = &normalized_root[..normalized_root.len() - 1];
+                    }
+
+                    // Only redirect if the normalized path actually matches a
+                    // mounted handler; otherwise leave the request alone and
+                    // let normal dispatch report the (same) not-found result.
+                    if self.inner.get_ancestor(normalized_root).is_some() {
+                        let mut url = req.url.clone();
+                        url.path = normalized;
+
+                        let status = match req.method {
+                            method::Get | method::Head => status::MovedPermanently,
+                            _ => status::PermanentRedirect
+                        };
+
+                        let mut response = Response::with(status);
+                        response.headers.set(headers::Location(url.to_string()));
+                        return Ok(response);
+                    }
+                }
+            }
+        }
+
         let original = req.url.path.clone();
 
         // If present, remove the trailing empty string (which represents a trailing slash).
@@ -95,13 +399,6 @@ impl Handler for Mount {
             root = &root[..root.len() - 1];
         }
 
-        // Find the matching handler.
-        let matched = match self.inner.get_ancestor(root) {
-            Some(matched) => matched,
-            None => return Err(IronError::new(NoMatch, status::NotFound))
-        };
-
-        // We have a match, so fire off the child.
         // If another mount middleware hasn't already, insert the unmodified url
         // into the extensions as the "original url".
         let is_outer_mount = !req.extensions.contains::<OriginalUrl>();
@@ -111,38 +408,110 @@ impl Handler for Mount {
 
             req.extensions.insert::<OriginalUrl>(req.url.clone());
             req.extensions.insert::<VirtualRoot>(root_url);
-        } else {
-            req.extensions.get_mut::<VirtualRoot>().map(|old| {
-                old.path.push_all(root);
-            });
         }
 
-        // Remove the prefix from the request's path before passing it to the mounted
-        // handler. If the prefix is entirely removed and no trailing slash was present,
-        // the new path will be the empty list.
-        //
-        // For the purposes of redirection, conveying that the path did not include
-        // a trailing slash is more important than providing a non-empty list.
-        req.url.path = req.url.path.as_slice()[matched.length..].to_vec();
+        // Walk back up the trie, from the most specific matching prefix to the
+        // least, giving each mounted handler a chance to serve the request.
+        // A handler "passes" by returning a `NoMatch` or `Pass` error; `Mount`
+        // then undoes the URL munging it did for that attempt and tries the
+        // next shorter prefix.
+        let mut prefix_len = root.len();
+        let mut last_err = IronError::new(NoMatch, status::NotFound);
+        let mut have_decline = false;
+
+        loop {
+            let matched = match self.inner.get(&root[..prefix_len]) {
+                Some(matched) => matched,
+                None => {
+                    if prefix_len == 0 { break; }
+                    prefix_len -= 1;
+                    continue;
+                }
+            };
+
+            // A prefix matched, but if there's no handler for this request's
+            // method and no verb-agnostic default, treat it the same as a
+            // `Pass`: remember a `MethodNotAllowed` response as this
+            // attempt's decline, then fall through to a shorter prefix,
+            // which may still have a handler willing to serve this method.
+            let handler = match matched.handler_for(&req.method) {
+                Some(handler) => handler,
+                None => {
+                    if !have_decline {
+                        last_err = method_not_allowed(matched);
+                        have_decline = true;
+                    }
+
+                    if prefix_len == 0 { break; }
+                    prefix_len -= 1;
+                    continue;
+                }
+            };
+
+            if !is_outer_mount {
+                req.extensions.get_mut::<VirtualRoot>().map(|old| {
+                    old.path.push_all(&root[..prefix_len]);
+                });
+            }
 
-        let res = matched.handler.handle(req);
+            // Remove the prefix from the request's path before passing it to the mounted
+            // handler. If the prefix is entirely removed and no trailing slash was present,
+            // the new path will be the empty list.
+            //
+            // For the purposes of redirection, conveying that the path did not include
+            // a trailing slash is more important than providing a non-empty list.
+            req.url.path = original.as_slice()[matched.length..].to_vec();
 
-        // Reverse the URL munging, for future middleware.
-        req.url.path = original.clone();
+            let res = handler.handle(req);
 
-        // If this mount middleware is the outermost mount middleware,
-        // remove the original url from the extensions map to prevent leakage.
+            // Reverse the URL munging, for future middleware (or the next attempt).
+            req.url.path = original.clone();
+            if !is_outer_mount {
+                req.extensions.get_mut::<VirtualRoot>().map(|old| {
+                    let old_len = old.path.len();
+                    old.path.truncate(old_len - prefix_len);
+                });
+            }
+
+            match res {
+                Ok(response) => {
+                    if is_outer_mount {
+                        req.extensions.remove::<OriginalUrl>();
+                        req.extensions.remove::<VirtualRoot>();
+                    }
+                    return Ok(response);
+                }
+                Err(err) => {
+                    if !is_fallthrough(&err) {
+                        if is_outer_mount {
+                            req.extensions.remove::<OriginalUrl>();
+                            req.extensions.remove::<VirtualRoot>();
+                        }
+                        return Err(err);
+                    }
+
+                    // Keep only the *first* (most-specific) decline: once a
+                    // shorter prefix also declines, its error is less useful
+                    // to the caller than the one from the handler that came
+                    // closest to matching.
+                    if !have_decline {
+                        last_err = err;
+                        have_decline = true;
+                    }
+
+                    if prefix_len == 0 { break; }
+                    prefix_len -= 1;
+                }
+            }
+        }
+
+        // Every matching prefix passed (or none matched at all); report the
+        // most specific handler's decline, or `NoMatch` if nothing matched.
         if is_outer_mount {
             req.extensions.remove::<OriginalUrl>();
             req.extensions.remove::<VirtualRoot>();
-        } else {
-            req.extensions.get_mut::<VirtualRoot>().map(|old| {
-                let old_len = old.path.len();
-                old.path.truncate(old_len - root.len());
-            });
         }
-
-        res
+        Err(last_err)
     }
 }
 
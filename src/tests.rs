@@ -1,22 +1,27 @@
 pub use iron::prelude::*;
 pub use iron::Handler;
 pub use iron::{status, method};
+pub use iron::headers::Location;
 
 // FIXME(reem): Write tests for OriginalUrl and VirtualRoot
-pub use {Mount, OriginalUrl, VirtualRoot, NoMatch};
+pub use {Mount, OriginalUrl, VirtualRoot, NoMatch, Pass, TrailingSlash, NormalizeMode};
 
 pub fn at(mount: &Mount, url: &str) -> Result<String, IronError> {
+    at_method(mount, method::Get, url).map(|res| {
+       res.body.unwrap_or(Box::new(::std::old_io::util::NullReader)).read_to_string().unwrap()
+    })
+}
+
+pub fn at_method(mount: &Mount, method: method::Method, url: &str) -> Result<Response, IronError> {
     use std::old_io::util::NullReader;
     use itest::mock::request;
     use iron::Url;
 
     let url = Url::parse(&format!("http://localhost:3000{}", url)).unwrap();
     let rdr = &mut NullReader;
-    let mut req = request::new(method::Get, url, rdr);
+    let mut req = request::new(method, url, rdr);
 
-    mount.handle(&mut req).map(|res| {
-       res.body.unwrap_or(Box::new(NullReader)).read_to_string().unwrap()
-    })
+    mount.handle(&mut req)
 }
 
 describe! mount {
@@ -73,5 +78,194 @@ describe! mount {
         assert_eq!(err.response.status, Some(status::NotFound));
         err.error.downcast::<NoMatch>().unwrap();
     }
+
+    it "should fall through to a less-specific mount when a handler passes" {
+        let mut mount = Mount::new();
+
+        mount.on("/hello", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "outer")))
+        });
+
+        mount.on("/hello/world", |_: &mut Request| {
+            Err(IronError::new(Pass, status::NotFound))
+        });
+
+        assert_eq!(&*at(&mount, "/hello/world").unwrap(), "outer");
+    }
+
+    it "should dispatch to a method-specific handler" {
+        let mut mount = Mount::new();
+
+        mount.on("/api", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "default")))
+        });
+
+        mount.on_method(method::Post, "/api", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "posted")))
+        });
+
+        assert_eq!(&*at(&mount, "/api").unwrap(), "default");
+
+        let res = at_method(&mount, method::Post, "/api").unwrap();
+        assert_eq!(
+            &*res.body.unwrap().read_to_string().unwrap(),
+            "posted"
+        );
+    }
+
+    it "should respond with MethodNotAllowed when the verb has no handler or default" {
+        let mut mount = Mount::new();
+
+        mount.on_method(method::Post, "/api", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "posted")))
+        });
+
+        let res = at_method(&mount, method::Get, "/api").unwrap_err();
+        assert_eq!(res.response.status, Some(status::MethodNotAllowed));
+    }
+
+    it "should fall through to a less-specific mount when the method doesn't match" {
+        let mut mount = Mount::new();
+
+        mount.on("/api", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "fallback")))
+        });
+
+        mount.on_method(method::Post, "/api/widgets", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "posted")))
+        });
+
+        assert_eq!(&*at(&mount, "/api/widgets").unwrap(), "fallback");
+    }
+
+    it "should redirect to the normalized path when trimming" {
+        let mut mount = Mount::new();
+        mount.normalize(TrailingSlash::Redirect(NormalizeMode::Trim));
+
+        mount.on("/hello", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "hello")))
+        });
+
+        let res = at_method(&mount, method::Get, "/hello/").unwrap();
+        assert_eq!(res.status, Some(status::MovedPermanently));
+        assert_eq!(
+            res.headers.get::<Location>().unwrap().as_slice(),
+            "http://localhost:3000/hello"
+        );
+    }
+
+    it "should not redirect a request that's already normalized" {
+        let mut mount = Mount::new();
+        mount.normalize(TrailingSlash::Redirect(NormalizeMode::Trim));
+
+        mount.on("/hello", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "hello")))
+        });
+
+        assert_eq!(&*at(&mount, "/hello").unwrap(), "hello");
+    }
+
+    it "should not redirect when the normalized path matches no mount" {
+        let mut mount = Mount::new();
+        mount.normalize(TrailingSlash::Redirect(NormalizeMode::Trim));
+
+        let res = at_method(&mount, method::Get, "/notfound/").unwrap_err();
+        assert_eq!(res.response.status, Some(status::NotFound));
+    }
+
+    it "should generate a url for a named mount" {
+        let mut mount = Mount::new();
+
+        mount.on_named("widgets", "/api/widgets", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "widgets")))
+        });
+
+        let url = mount.url_for("widgets", &["42"]).unwrap();
+        assert_eq!(&*url.path.connect("/"), "api/widgets/42");
+
+        assert!(mount.url_for("nonexistent", &[]).is_none());
+    }
+
+    it "should derive url_for_request's base from the consumed mount prefix" {
+        use iron::Url;
+        use itest::mock::request;
+        use std::old_io::util::NullReader;
+
+        let mut mount = Mount::new();
+        mount.on_named("widget", "/widgets", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "widget")))
+        });
+
+        let original = Url::parse("http://localhost:3000/api/widgets").unwrap();
+        let mut req = request::new(method::Get, original.clone(), &mut NullReader);
+
+        // Simulate the state this mount's handler sees once nested inside an
+        // outer mount at "/api": the outer mount recorded the whole incoming
+        // path as `OriginalUrl` and trimmed its own prefix off `req.url.path`,
+        // leaving more of the path still to be consumed than just the prefix.
+        req.extensions.insert::<OriginalUrl>(original);
+        req.url.path = vec!["widgets".to_string()];
+
+        let url = mount.url_for_request(&req, "widget", &["42"]).unwrap();
+        assert_eq!(&*url.path.connect("/"), "api/widgets/42");
+    }
+
+    it "should rebase a mount under a new prefix" {
+        let mut sub = Mount::new();
+        sub.on_named("widget", "/widgets", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "widget")))
+        });
+
+        let rebased = sub.rebase("/api");
+
+        assert_eq!(&*at(&rebased, "/api/widgets").unwrap(), "widget");
+        assert_eq!(
+            &*rebased.url_for("widget", &[]).unwrap().path.connect("/"),
+            "api/widgets"
+        );
+    }
+
+    it "should extend a mount with another under a new prefix" {
+        let mut sub = Mount::new();
+        sub.on("/widgets", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "widget")))
+        });
+
+        let mut mount = Mount::new();
+        mount.on("/hello", |_: &mut Request| {
+            Ok(Response::with((status::Ok, "hello")))
+        });
+        mount.extend(sub, "/api");
+
+        assert_eq!(&*at(&mount, "/hello").unwrap(), "hello");
+        assert_eq!(&*at(&mount, "/api/widgets").unwrap(), "widget");
+    }
+
+    it "should propagate the most specific error when every match passes" {
+        let mut mount = Mount::new();
+
+        mount.on("/hello/world", |_: &mut Request| {
+            Err(IronError::new(Pass, status::NotFound))
+        });
+
+        let err = at(&mount, "/hello/world").unwrap_err();
+        err.error.downcast::<Pass>().unwrap();
+    }
+
+    it "should propagate the *most* specific decline, not the least specific" {
+        let mut mount = Mount::new();
+
+        // Distinguish the two declines by status, since both use `Pass`.
+        mount.on("/hello", |_: &mut Request| {
+            Err(IronError::new(Pass, status::BadGateway))
+        });
+
+        mount.on("/hello/world", |_: &mut Request| {
+            Err(IronError::new(Pass, status::NotFound))
+        });
+
+        let err = at(&mount, "/hello/world").unwrap_err();
+        assert_eq!(err.response.status, Some(status::NotFound));
+    }
 }
 